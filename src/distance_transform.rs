@@ -0,0 +1,378 @@
+//! Exact squared Euclidean distance transform, after Felzenszwalb & Huttenlocher's
+//! "Distance Transforms of Sampled Functions": a separable, linear-time replacement
+//! for scanning a box around every pixel.
+
+/// Sentinel squared-distance for "no feature pixel in this grid". Must be finite: the
+/// lower envelope subtracts two `f[]` values, and `INFINITY - INFINITY` is NaN, which
+/// poisons the envelope. Large enough to dominate any real squared distance in a grid
+/// no bigger than `u32::MAX` on a side.
+const NO_FEATURE: f32 = 1.0e20;
+
+const ENVELOPE_BOUND: f32 = f32::INFINITY;
+
+/// Computes the squared distance (in pixels²) from every pixel in a `width` x `height`
+/// grid to the closest "seed" pixel, in O(width * height). `seed` returns `None` for
+/// pixels that aren't a seed, or `Some(base_distance_squared)` for ones that are; a
+/// plain feature pixel uses `Some(0.0)`, while a sub-pixel seed can report a small
+/// positive base distance to place the true feature point off the pixel center.
+///
+/// The result is a row-major grid: index `(y * width + x)` holds the squared distance
+/// for pixel `(x, y)`.
+pub fn squared_distance_transform(
+    width: u32,
+    height: u32,
+    seed: impl Fn(u32, u32) -> Option<f32>,
+) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+    let mut grid = vec![0.0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            grid[y * width + x] = seed(x as u32, y as u32).unwrap_or(NO_FEATURE);
+        }
+    }
+
+    // Transform each column, then each row of the column-transformed result. Since the
+    // transform is separable this two-pass approach gives the exact 2D squared distance.
+    let mut column = vec![0.0f32; height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = grid[y * width + x];
+        }
+        let transformed = lower_envelope_1d(&column);
+        for (y, value) in transformed.into_iter().enumerate() {
+            grid[y * width + x] = value;
+        }
+    }
+
+    let mut row = vec![0.0f32; width];
+    for y in 0..height {
+        row.copy_from_slice(&grid[y * width..(y + 1) * width]);
+        let transformed = lower_envelope_1d(&row);
+        grid[y * width..(y + 1) * width].copy_from_slice(&transformed);
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(width: u32, height: u32, seeds: &[(u32, u32)]) -> Vec<f32> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                seeds
+                    .iter()
+                    .map(|&(sx, sy)| {
+                        let dx = x as f32 - sx as f32;
+                        let dy = y as f32 - sy as f32;
+                        dx * dx + dy * dy
+                    })
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_brute_force_over_scattered_seeds() {
+        let (width, height) = (11, 9);
+        let seeds = [(1, 1), (9, 2), (4, 7), (0, 8)];
+        let expected = brute_force(width, height, &seeds);
+
+        let got = squared_distance_transform(width, height, |x, y| {
+            seeds.contains(&(x, y)).then_some(0.0)
+        });
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn single_seed() {
+        let (width, height) = (5, 5);
+        let expected = brute_force(width, height, &[(2, 2)]);
+
+        let got = squared_distance_transform(width, height, |x, y| (x == 2 && y == 2).then_some(0.0));
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn no_seeds_stays_at_the_sentinel_distance() {
+        let (width, height) = (4, 4);
+
+        let got = squared_distance_transform(width, height, |_, _| None);
+
+        assert!(got.iter().all(|&d| d >= NO_FEATURE));
+    }
+
+    #[test]
+    fn column_with_no_seed_still_finds_seeds_from_neighboring_columns() {
+        let (width, height) = (3, 3);
+        // Only the middle column has a seed; the outer two columns have none of their own,
+        // which is the case that produced `INFINITY - INFINITY` NaNs with an infinite sentinel.
+        let seeds = [(1, 1)];
+        let expected = brute_force(width, height, &seeds);
+
+        let got = squared_distance_transform(width, height, |x, y| seeds.contains(&(x, y)).then_some(0.0));
+
+        assert_eq!(got, expected);
+    }
+}
+
+/// Like [`squared_distance_transform`], but each seed reports a continuous feature
+/// coordinate `(fx, fy)` near `(x, y)` instead of being pinned to the pixel center. This
+/// lets a sub-pixel seed actually offset the boundary *along* its local gradient rather
+/// than just reporting an isotropic base distance, because the true feature position is
+/// carried through both passes instead of being collapsed to a scalar at the seed pixel.
+///
+/// Each column is solved first (nearest feature among that column's own seeds, by its
+/// vertical distance to `fy`), then each row takes the column pass's winning feature and
+/// its vertical distance and finds the nearest across columns, using the carried feature's
+/// `fx` as its true horizontal position rather than the column index. The column pass does
+/// not look at `fx`, so the result is exact only when the features of each column share
+/// the same `fx` (as pixel-center seeds do). Where they differ, a pixel can be assigned the
+/// feature that is vertically closest within its column rather than the closest one
+/// overall, and its distance then comes out larger than the true distance.
+pub fn squared_distance_transform_with_features(
+    width: u32,
+    height: u32,
+    seed: impl Fn(u32, u32) -> Option<(f32, f32)>,
+) -> Vec<f32> {
+    let (width, height) = (width as usize, height as usize);
+
+    // Column pass: within each column, find the nearest seed assigned to that column by
+    // its true fy (height 0, since a seed is zero distance from itself), and
+    // remember which feature's fx realized it so the row pass can use it as that
+    // feature's true horizontal position instead of the column index.
+    let mut vertical_squared_distance = vec![NO_FEATURE; width * height];
+    let mut winning_feature_x = vec![0.0f32; width * height];
+
+    for x in 0..width {
+        let mut sites: Vec<(f32, f32, f32)> = (0..height)
+            .filter_map(|y| seed(x as u32, y as u32))
+            .map(|(fx, fy)| (fy, 0.0, fx))
+            .collect();
+        if sites.is_empty() {
+            continue;
+        }
+        sites.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("feature position can't be NaN"));
+        deduplicate_positions(&mut sites);
+
+        let queries: Vec<f32> = (0..height).map(|y| y as f32).collect();
+        for (y, (squared_distance, fx)) in lower_envelope(&sites, &queries).into_iter().enumerate() {
+            vertical_squared_distance[y * width + x] = squared_distance;
+            winning_feature_x[y * width + x] = fx;
+        }
+    }
+
+    // Row pass: across columns, find the nearest carried feature by its true fx, added to
+    // the vertical distance the column pass already computed for it.
+    let mut grid = vec![0.0f32; width * height];
+    for y in 0..height {
+        let mut sites: Vec<(f32, f32, f32)> = (0..width)
+            .filter(|&x| vertical_squared_distance[y * width + x] < NO_FEATURE)
+            .map(|x| {
+                (
+                    winning_feature_x[y * width + x],
+                    vertical_squared_distance[y * width + x],
+                    0.0,
+                )
+            })
+            .collect();
+        if sites.is_empty() {
+            grid[y * width..(y + 1) * width].fill(NO_FEATURE);
+            continue;
+        }
+        sites.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("feature position can't be NaN"));
+        deduplicate_positions(&mut sites);
+
+        let queries: Vec<f32> = (0..width).map(|x| x as f32).collect();
+        for (x, (squared_distance, _)) in lower_envelope(&sites, &queries).into_iter().enumerate() {
+            grid[y * width + x] = squared_distance;
+        }
+    }
+
+    grid
+}
+
+/// `lower_envelope` requires strictly increasing site positions; two sub-pixel seeds can
+/// occasionally land on the same position after rounding. Nudge later duplicates by a
+/// negligible epsilon so the envelope construction never divides by zero.
+fn deduplicate_positions(sites: &mut [(f32, f32, f32)]) {
+    for i in 1..sites.len() {
+        if sites[i].0 <= sites[i - 1].0 {
+            sites[i].0 = sites[i - 1].0 + f32::EPSILON.max(sites[i - 1].0.abs() * f32::EPSILON) + 1e-4;
+        }
+    }
+}
+
+/// Lower envelope of parabolas rooted at arbitrary `(position, height, payload)` sites
+/// (not necessarily at integer positions, unlike [`lower_envelope_1d`]), evaluated at
+/// each of `queries`. Returns, per query, the winning `(squared_distance + height,
+/// payload)`. Requires `sites` sorted by position with no two sharing a position, and
+/// `queries` non-decreasing.
+fn lower_envelope(sites: &[(f32, f32, f32)], queries: &[f32]) -> Vec<(f32, f32)> {
+    let n = sites.len();
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+
+    let mut k = 0usize;
+    z[0] = -ENVELOPE_BOUND;
+    z[1] = ENVELOPE_BOUND;
+
+    for i in 1..n {
+        loop {
+            let (pos_i, height_i, _) = sites[i];
+            let (pos_k, height_k, _) = sites[v[k]];
+            let s = ((height_i + pos_i * pos_i) - (height_k + pos_k * pos_k)) / (2.0 * pos_i - 2.0 * pos_k);
+            if s <= z[k] {
+                k -= 1;
+            } else {
+                k += 1;
+                v[k] = i;
+                z[k] = s;
+                z[k + 1] = ENVELOPE_BOUND;
+                break;
+            }
+        }
+    }
+
+    let mut results = Vec::with_capacity(queries.len());
+    let mut k = 0usize;
+    for &q in queries {
+        while z[k + 1] < q {
+            k += 1;
+        }
+        let (pos, height, payload) = sites[v[k]];
+        let d = q - pos;
+        results.push((d * d + height, payload));
+    }
+    results
+}
+
+/// One-dimensional lower envelope of parabolas rooted at each sample `f[q]`, evaluated
+/// at every position. This is the core step of the Felzenszwalb-Huttenlocher transform.
+fn lower_envelope_1d(f: &[f32]) -> Vec<f32> {
+    let n = f.len();
+    let mut d = vec![0.0f32; n];
+    // v[k] holds the location of the k:th parabola in the lower envelope, z[k] the
+    // left boundary (in sample space) from which that parabola is the lowest.
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+
+    let mut k = 0usize;
+    z[0] = -ENVELOPE_BOUND;
+    z[1] = ENVELOPE_BOUND;
+
+    for q in 1..n {
+        loop {
+            let vk = v[k] as f32;
+            let s = ((f[q] + (q as f32).powi(2)) - (f[v[k]] + vk.powi(2))) / (2.0 * q as f32 - 2.0 * vk);
+            if s <= z[k] {
+                k -= 1;
+            } else {
+                k += 1;
+                v[k] = q;
+                z[k] = s;
+                z[k + 1] = ENVELOPE_BOUND;
+                break;
+            }
+        }
+    }
+
+    k = 0;
+    for (q, d_q) in d.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let dq = q as f32 - v[k] as f32;
+        *d_q = dq * dq + f[v[k]];
+    }
+
+    d
+}
+
+#[cfg(test)]
+mod feature_tests {
+    use super::*;
+
+    fn brute_force_features(width: u32, height: u32, features: &[(f32, f32)]) -> Vec<f32> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                features
+                    .iter()
+                    .map(|&(fx, fy)| {
+                        let dx = x as f32 - fx;
+                        let dy = y as f32 - fy;
+                        dx * dx + dy * dy
+                    })
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pixel_center_seeds_match_the_plain_transform() {
+        let (width, height) = (9, 7);
+        let seeds = [(1, 1), (7, 2), (3, 5)];
+
+        let plain = squared_distance_transform(width, height, |x, y| {
+            seeds.contains(&(x, y)).then_some(0.0)
+        });
+        let with_features = squared_distance_transform_with_features(width, height, |x, y| {
+            seeds.contains(&(x, y)).then_some((x as f32, y as f32))
+        });
+
+        assert_eq!(with_features, plain);
+    }
+
+    #[test]
+    fn off_center_feature_matches_brute_force() {
+        let (width, height) = (6, 6);
+        // A single feature offset half a pixel down-right of (2, 2).
+        let feature = (2.5, 2.5);
+        let expected = brute_force_features(width, height, &[feature]);
+
+        let got = squared_distance_transform_with_features(width, height, |x, y| {
+            (x == 2 && y == 2).then_some(feature)
+        });
+
+        for (got, expected) in got.iter().zip(expected.iter()) {
+            assert!((got - expected).abs() < 1e-3, "{got} != {expected}");
+        }
+    }
+
+    #[test]
+    fn diagonal_sub_pixel_offset_beats_snapping_to_the_pixel_center() {
+        // A feature pixel at (3, 3) whose true edge sits diagonally towards (4, 4), as a
+        // sub-pixel edge seed would report for a 45-degree anti-aliased boundary.
+        let feature = (3.3, 3.7);
+        let (width, height) = (8, 8);
+        let expected = brute_force_features(width, height, &[feature]);
+
+        let with_features = squared_distance_transform_with_features(width, height, |x, y| {
+            (x == 3 && y == 3).then_some(feature)
+        });
+        let hard_thresholded =
+            squared_distance_transform(width, height, |x, y| (x == 3 && y == 3).then_some(0.0));
+
+        let error_with_features: f32 = with_features
+            .iter()
+            .zip(expected.iter())
+            .map(|(got, want)| (got - want).abs())
+            .sum();
+        let error_hard_thresholded: f32 = hard_thresholded
+            .iter()
+            .zip(expected.iter())
+            .map(|(got, want)| (got - want).abs())
+            .sum();
+
+        assert!(
+            error_with_features < error_hard_thresholded,
+            "sub-pixel offset ({error_with_features}) should track the true diagonal edge \
+             more closely than snapping to the pixel center ({error_hard_thresholded})"
+        );
+    }
+}
@@ -1,16 +1,42 @@
 use std::f32::consts::PI;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Instant;
 
 use clap::Parser;
 use image::io::Reader as ImageReader;
 use image::{ImageBuffer, Luma};
+use rayon::prelude::*;
 
+mod distance_transform;
 mod neighbor_iterator;
+mod vp_tree;
 
 const BLACK: u16 = 0;
 
+/// Which algorithm to use for finding, per output pixel, the closest black pixel.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum NearestBackend {
+    /// Exact distance transform over the whole image. O(width * height), independent of
+    /// how much black the image contains.
+    Edt,
+    /// Vantage-point tree over the black pixels. Faster than `edt` for sparse inputs such
+    /// as text or line art, where most of the image is white.
+    Vptree,
+}
+
+/// Which representation of the stamped region to write to disk.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// The original negative/positive die form pair, faded over `--fade-distance`.
+    Heightmap,
+    /// A single signed distance field of the stamped region, in millimeters and clamped
+    /// to `--saturation-distance`, for CAD/CAM or shader pipelines that want to
+    /// re-threshold or offset the contour themselves.
+    Sdf,
+}
+
 #[derive(clap::Parser, Debug)]
 struct Args {
     /// Input image file to create stamp dies from. Should be black and white. Black is where the sheet
@@ -35,6 +61,26 @@ struct Args {
     /// The default value of 0.1 mm per pixel gives enough resolution for most practical use cases.
     #[arg(long, default_value_t = 10.0)]
     pixels_per_mm: f32,
+
+    /// Treat grayscale input as fractional coverage and place edges at sub-pixel positions
+    /// instead of hard-thresholding at black. Removes staircase artifacts along diagonal
+    /// and curved edges, at the cost of requiring anti-aliased input. Only supported with
+    /// `--nearest-backend edt`.
+    #[arg(long, default_value_t = false)]
+    subpixel_edges: bool,
+
+    /// Which algorithm to use to find the closest black pixel for each output pixel.
+    #[arg(long, value_enum, default_value_t = NearestBackend::Edt)]
+    nearest_backend: NearestBackend,
+
+    /// Which representation of the stamped region to write to disk.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Heightmap)]
+    output_format: OutputFormat,
+
+    /// Clamp distance (in millimeters) from the contour for `--output-format sdf`.
+    /// Distances further away than this saturate to the same encoded value.
+    #[arg(long, default_value_t = 5.0)]
+    saturation_distance: f32,
 }
 
 /// Parses the command line arguments and check that they are sane. Prints an error
@@ -62,16 +108,18 @@ fn parse_args() -> Args {
         eprintln!("Invalid value for pixels per mm. Has to be positive");
         process::exit(1);
     }
+    if args.subpixel_edges && args.nearest_backend != NearestBackend::Edt {
+        eprintln!("--subpixel-edges is only supported with --nearest-backend edt");
+        process::exit(1);
+    }
+    if !args.saturation_distance.is_normal() {
+        eprintln!("Invalid value for saturation distance. Has to be positive");
+        process::exit(1);
+    }
 
     args
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-struct PixelCoordinate {
-    pub x: u32,
-    pub y: u32,
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = parse_args();
 
@@ -80,34 +128,80 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (width, height) = input.dimensions();
     println!("Hello, world! {width}x{height}");
 
-    let negative_form_start = Instant::now();
-    let negative_form = compute_negative_form(&input, args.fade_distance, args.pixels_per_mm);
-    println!(
-        "Computing negative form took {} ms",
-        negative_form_start.elapsed().as_millis()
-    );
-
-    let positive_form_start = Instant::now();
-    let positive_form = compute_positive_form(
-        &negative_form,
-        args.punch_out_depth,
-        args.sheet_thickness,
-        args.pixels_per_mm,
-    );
-    println!(
-        "Computing positive form took {} ms",
-        positive_form_start.elapsed().as_millis()
-    );
+    match args.output_format {
+        OutputFormat::Heightmap => {
+            let negative_form_start = Instant::now();
+            let negative_form = compute_negative_form(
+                &input,
+                args.fade_distance,
+                args.pixels_per_mm,
+                args.subpixel_edges,
+                args.nearest_backend,
+            );
+            println!(
+                "Computing negative form took {} ms",
+                negative_form_start.elapsed().as_millis()
+            );
+
+            let positive_form_start = Instant::now();
+            let positive_form = compute_positive_form(
+                &negative_form,
+                args.punch_out_depth,
+                args.sheet_thickness,
+                args.pixels_per_mm,
+            );
+            println!(
+                "Computing positive form took {} ms",
+                positive_form_start.elapsed().as_millis()
+            );
+
+            let negative_output_path = output_path(&args.input, "negative")
+                .expect("Unable to convert input path to output path");
+            negative_form.save_with_format(negative_output_path, image::ImageFormat::Png)?;
+
+            let positive_output_path = output_path(&args.input, "positive")
+                .expect("Unable to convert input path to output path");
+            positive_form.save_with_format(positive_output_path, image::ImageFormat::Png)?;
+        }
+        OutputFormat::Sdf => {
+            let sdf_start = Instant::now();
+            let sdf = compute_signed_distance_field(
+                &input,
+                args.pixels_per_mm,
+                args.saturation_distance,
+            );
+            println!(
+                "Computing signed distance field took {} ms",
+                sdf_start.elapsed().as_millis()
+            );
+
+            let sdf_output_path =
+                output_path(&args.input, "sdf").expect("Unable to convert input path to output path");
+            sdf.save_with_format(sdf_output_path, image::ImageFormat::Png)?;
+        }
+    }
 
-    let negative_output_path =
-        output_path(&args.input, "negative").expect("Unable to convert input path to output path");
-    negative_form.save_with_format(negative_output_path, image::ImageFormat::Png)?;
+    Ok(())
+}
 
-    let positive_output_path =
-        output_path(&args.input, "positive").expect("Unable to convert input path to output path");
-    positive_form.save_with_format(positive_output_path, image::ImageFormat::Png)?;
+/// Holds whichever nearest-black-pixel structure `--nearest-backend` built, so the pixel
+/// loop in `compute_negative_form` doesn't need to care which one it's querying.
+enum NearestBlackPixels {
+    /// Squared distance (in pixels²) to the closest black pixel, indexed `y * width + x`.
+    Edt(Vec<f32>),
+    VpTree(vp_tree::VpTree),
+}
 
-    Ok(())
+impl NearestBlackPixels {
+    fn squared_distance_pixels(&self, width: u32, x: u32, y: u32) -> f32 {
+        match self {
+            NearestBlackPixels::Edt(grid) => grid[(y * width + x) as usize],
+            NearestBlackPixels::VpTree(tree) => tree
+                .nearest(vp_tree::Point { x, y })
+                .map(|nearest| nearest.squared_distance)
+                .unwrap_or(f32::INFINITY),
+        }
+    }
 }
 
 /// Computes and returns the image buffer for the negative form.
@@ -115,37 +209,74 @@ fn compute_negative_form(
     input: &ImageBuffer<Luma<u16>, Vec<u16>>,
     fade_distance: f32,
     pixels_per_mm: f32,
+    subpixel_edges: bool,
+    nearest_backend: NearestBackend,
 ) -> ImageBuffer<Luma<u16>, Vec<u16>> {
     let (width, height) = input.dimensions();
     let mut negative_form: ImageBuffer<Luma<u16>, Vec<_>> = ImageBuffer::new(width, height);
 
-    let mut last_reported_percentage = 0;
-    for output_y in 0..height {
-        let percentage = (output_y as f32 / height as f32 * 100.0).floor() as u32;
-        if percentage > last_reported_percentage {
-            last_reported_percentage = percentage;
-            println!("{percentage}%");
-        }
-        for output_x in 0..width {
-            // The negative form should be flipped horizontally to be correct.
-            let input_coordinate = PixelCoordinate {
-                x: width - 1 - output_x,
-                y: output_y,
-            };
-            let output_color = if let Some(distance_to_black_mm) =
-                closest_black_pixel(&input, input_coordinate, fade_distance, pixels_per_mm)
-            {
-                fade_fn(distance_to_black_mm, fade_distance)
-            } else {
-                u16::MAX
-            };
-            negative_form.put_pixel(output_x, output_y, Luma([output_color]));
+    let nearest_black_pixels = match nearest_backend {
+        // Linear-time exact distance transform instead of scanning a box per pixel. This
+        // makes the negative form computation independent of `fade_distance`, so users can
+        // pick much larger fade distances without the runtime exploding.
+        NearestBackend::Edt => NearestBlackPixels::Edt(if subpixel_edges {
+            distance_transform::squared_distance_transform_with_features(width, height, |x, y| {
+                subpixel_feature_position(input, x, y)
+            })
+        } else {
+            distance_transform::squared_distance_transform(width, height, |x, y| {
+                (input.get_pixel(x, y).0[0] == BLACK).then_some(0.0)
+            })
+        }),
+        // Sub-linear queries for sparse inputs (text, logos, line art) where most of the
+        // image is white and a full-image scan wastes work on empty regions.
+        NearestBackend::Vptree => {
+            let mut black_pixels = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if input.get_pixel(x, y).0[0] == BLACK {
+                        black_pixels.push(vp_tree::Point { x, y });
+                    }
+                }
+            }
+            NearestBlackPixels::VpTree(vp_tree::VpTree::build(black_pixels))
         }
-    }
+    };
+
+    let last_reported_percentage = AtomicU32::new(0);
+    negative_form
+        .par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(output_y, row)| {
+            let output_y = output_y as u32;
+            report_row_progress(output_y, height, &last_reported_percentage);
+            for (output_x, pixel) in row.iter_mut().enumerate() {
+                let output_x = output_x as u32;
+                // The negative form should be flipped horizontally to be correct.
+                let input_x = width - 1 - output_x;
+                let distance_to_black_mm = nearest_black_pixels
+                    .squared_distance_pixels(width, input_x, output_y)
+                    .sqrt()
+                    / pixels_per_mm;
+                *pixel = fade_fn(distance_to_black_mm, fade_distance);
+            }
+        });
 
     negative_form
 }
 
+/// Prints `{percentage}%` to stdout the first time each row computation crosses into a
+/// new whole percentage of `total_rows`. Since rows are processed in parallel this is
+/// only roughly monotonic: `last_reported_percentage` is shared across threads so each
+/// percentage is printed at most once, but not strictly in row order.
+fn report_row_progress(row: u32, total_rows: u32, last_reported_percentage: &AtomicU32) {
+    let percentage = (row as f32 / total_rows as f32 * 100.0).floor() as u32;
+    let previous = last_reported_percentage.fetch_max(percentage, Ordering::Relaxed);
+    if percentage > previous {
+        println!("{percentage}%");
+    }
+}
+
 fn compute_positive_form(
     negative_form: &ImageBuffer<Luma<u16>, Vec<u16>>,
     punch_out_depth: f32,
@@ -158,65 +289,116 @@ fn compute_positive_form(
     let sheet_thickness_neighbors =
         neighbor_iterator::Neighbors::new(sheet_thickness * pixels_per_mm);
 
-    let mut last_reported_percentage = 0;
-    for positive_y in 0..height {
-        let percentage = (positive_y as f32 / height as f32 * 100.0).floor() as u32;
-        if percentage > last_reported_percentage {
-            last_reported_percentage = percentage;
-            println!("{percentage}%");
-        }
-        for positive_x in 0..width {
-            let mut positive_z_mm = 0.0;
-            for (offset, distance_pixels) in &sheet_thickness_neighbors {
-                let negative_y = positive_y as i32 + offset.y;
-                // Since the negative form is horizontally flipped we have to read the negative
-                // form from right to left.
-                let negative_x = width as i32 - 1 - (positive_x as i32 + offset.x);
-                // Skip pixels outside the image
-                if negative_y < 0
-                    || negative_y >= height as i32
-                    || negative_x < 0
-                    || negative_x >= width as i32
-                {
-                    continue;
-                }
+    let last_reported_percentage = AtomicU32::new(0);
+    positive_form
+        .par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(positive_y, row)| {
+            let positive_y = positive_y as u32;
+            report_row_progress(positive_y, height, &last_reported_percentage);
+            for (positive_x, pixel) in row.iter_mut().enumerate() {
+                let positive_x = positive_x as u32;
+                let mut positive_z_mm = 0.0;
+                for (offset, distance_pixels) in &sheet_thickness_neighbors {
+                    let negative_y = positive_y as i32 + offset.y;
+                    // Since the negative form is horizontally flipped we have to read the negative
+                    // form from right to left.
+                    let negative_x = width as i32 - 1 - (positive_x as i32 + offset.x);
+                    // Skip pixels outside the image
+                    if negative_y < 0
+                        || negative_y >= height as i32
+                        || negative_x < 0
+                        || negative_x >= width as i32
+                    {
+                        continue;
+                    }
 
-                let xy_distance_mm = distance_pixels / pixels_per_mm;
-                let negative_z_mm = negative_form
-                    .get_pixel(negative_x as u32, negative_y as u32)
-                    .0[0] as f32
-                    / u16::MAX as f32
-                    * punch_out_depth;
-
-                // Compute the missing side of the triangle. The sheet thickness is the hypotenuse
-                // and the positive to negative xy-distance is one known side.
-                let required_z_diff_mm = ((sheet_thickness * sheet_thickness)
-                    - (xy_distance_mm * xy_distance_mm))
-                    .sqrt();
-                let required_z = negative_z_mm + required_z_diff_mm;
-                // Bump up positive_z_mm if required_z is higher than currently held value
-                if required_z > positive_z_mm {
-                    positive_z_mm = required_z;
-                }
-                // Abort early if we are already so high up that subsequent pixels can't push us higher.
-                // We can do this optimization since we know that `positive_z_mm` will only ever increase
-                // and `required_z_diff_mm` will only shrink towards zero.
-                if positive_z_mm > punch_out_depth + required_z_diff_mm {
-                    break;
+                    let xy_distance_mm = distance_pixels / pixels_per_mm;
+                    let negative_z_mm = negative_form
+                        .get_pixel(negative_x as u32, negative_y as u32)
+                        .0[0] as f32
+                        / u16::MAX as f32
+                        * punch_out_depth;
+
+                    // Compute the missing side of the triangle. The sheet thickness is the hypotenuse
+                    // and the positive to negative xy-distance is one known side.
+                    let required_z_diff_mm = ((sheet_thickness * sheet_thickness)
+                        - (xy_distance_mm * xy_distance_mm))
+                        .sqrt();
+                    let required_z = negative_z_mm + required_z_diff_mm;
+                    // Bump up positive_z_mm if required_z is higher than currently held value
+                    if required_z > positive_z_mm {
+                        positive_z_mm = required_z;
+                    }
+                    // Abort early if we are already so high up that subsequent pixels can't push us higher.
+                    // We can do this optimization since we know that `positive_z_mm` will only ever increase
+                    // and `required_z_diff_mm` will only shrink towards zero.
+                    if positive_z_mm > punch_out_depth + required_z_diff_mm {
+                        break;
+                    }
                 }
+                positive_z_mm -= sheet_thickness;
+                assert!(positive_z_mm >= 0.0);
+                assert!(positive_z_mm <= punch_out_depth);
+                *pixel = u16::MAX - ((positive_z_mm / punch_out_depth) * u16::MAX as f32) as u16;
             }
-            positive_z_mm -= sheet_thickness;
-            assert!(positive_z_mm >= 0.0);
-            assert!(positive_z_mm <= punch_out_depth);
-            let positive_pixel =
-                u16::MAX - ((positive_z_mm / punch_out_depth) * u16::MAX as f32) as u16;
-            positive_form.put_pixel(positive_x, positive_y, Luma([positive_pixel]));
-        }
-    }
+        });
 
     positive_form
 }
 
+/// Computes a signed distance field of the stamped (black) region: negative inside it,
+/// positive outside, in millimeters and clamped to `saturation_distance_mm`.
+fn compute_signed_distance_field(
+    input: &ImageBuffer<Luma<u16>, Vec<u16>>,
+    pixels_per_mm: f32,
+    saturation_distance_mm: f32,
+) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+    let (width, height) = input.dimensions();
+    let mut sdf: ImageBuffer<Luma<u16>, Vec<_>> = ImageBuffer::new(width, height);
+
+    // Outside distance: seeded from black pixels. Inside distance: seeded from everything
+    // else. Subtracting the two gives a field that's negative inside the black region.
+    let outside_squared_pixels = distance_transform::squared_distance_transform(
+        width,
+        height,
+        |x, y| (input.get_pixel(x, y).0[0] == BLACK).then_some(0.0),
+    );
+    let inside_squared_pixels = distance_transform::squared_distance_transform(
+        width,
+        height,
+        |x, y| (input.get_pixel(x, y).0[0] != BLACK).then_some(0.0),
+    );
+
+    let last_reported_percentage = AtomicU32::new(0);
+    sdf.par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(output_y, row)| {
+            let output_y = output_y as u32;
+            report_row_progress(output_y, height, &last_reported_percentage);
+            for (output_x, pixel) in row.iter_mut().enumerate() {
+                let output_x = output_x as u32;
+                // Flipped horizontally to match the negative heightmap form, so the sdf
+                // output overlays it pixel-for-pixel.
+                let input_x = width - 1 - output_x;
+                let index = (output_y * width + input_x) as usize;
+                let outside_mm = outside_squared_pixels[index].sqrt() / pixels_per_mm;
+                let inside_mm = inside_squared_pixels[index].sqrt() / pixels_per_mm;
+                let signed_distance_mm = outside_mm - inside_mm;
+                *pixel = encode_signed_distance(signed_distance_mm, saturation_distance_mm);
+            }
+        });
+
+    sdf
+}
+
+/// Encodes a signed distance in millimeters to `u16`, clamped to `saturation_distance_mm`
+/// and scaled so `0` maps near mid-gray (`32768`).
+fn encode_signed_distance(signed_distance_mm: f32, saturation_distance_mm: f32) -> u16 {
+    let clamped = signed_distance_mm.clamp(-saturation_distance_mm, saturation_distance_mm);
+    ((clamped / saturation_distance_mm) * 32767.0 + 32768.0) as u16
+}
+
 fn output_path(input_path: &Path, form_type: &str) -> Option<PathBuf> {
     let dir = input_path.parent()?;
     let mut filename = input_path.file_stem()?.to_owned();
@@ -224,53 +406,50 @@ fn output_path(input_path: &Path, form_type: &str) -> Option<PathBuf> {
     Some(dir.join(filename))
 }
 
-/// Returns the distance (in mm) from `coordinate` to the closest pixel that is black, in `image`. Only searches the `max_distance` closest pixels
-fn closest_black_pixel(
-    image: &ImageBuffer<Luma<u16>, Vec<u16>>,
-    coordinate: PixelCoordinate,
-    max_distance_mm: f32,
-    pixels_per_mm: f32,
-) -> Option<f32> {
-    let max_distance_pixels = (max_distance_mm * pixels_per_mm).floor() as u32;
-    let start_x = coordinate.x.saturating_sub(max_distance_pixels);
-    let end_x = coordinate
-        .x
-        .saturating_add(max_distance_pixels)
-        .min(image.width());
-    let start_y = coordinate.y.saturating_sub(max_distance_pixels);
-    let end_y = coordinate
-        .y
-        .saturating_add(max_distance_pixels)
-        .min(image.height());
-    let mut closest_location = None;
-    for other_y in start_y..end_y {
-        for other_x in start_x..end_x {
-            if image.get_pixel(other_x, other_y).0[0] == BLACK {
-                let distance = distance_mm(
-                    coordinate,
-                    PixelCoordinate {
-                        x: other_x,
-                        y: other_y,
-                    },
-                    pixels_per_mm,
-                );
-                if let Some(closest_location) = closest_location.as_mut() {
-                    if distance < *closest_location {
-                        *closest_location = distance;
-                    }
-                } else {
-                    closest_location = Some(distance);
-                }
-            }
-        }
+/// Returns the sub-pixel feature coordinate to seed the distance transform with at
+/// `(x, y)`, or `None` if the pixel isn't part of the black region at all.
+///
+/// Grayscale input is treated as fractional coverage of black: a fully black pixel is an
+/// ordinary seed at its own pixel center, a fully white pixel isn't a seed, and a
+/// partially covered pixel sits on the true edge somewhere within its footprint. We
+/// estimate the local unit gradient of the coverage field and offset the pixel center by
+/// `(0.5 - coverage)` along it, so a 50%-covered pixel's feature lands exactly on the
+/// edge. Unlike a scalar base distance, this coordinate is carried all the way through
+/// [`distance_transform::squared_distance_transform_with_features`], so the offset
+/// actually follows the gradient direction instead of just nudging the seed's distance.
+fn subpixel_feature_position(
+    input: &ImageBuffer<Luma<u16>, Vec<u16>>,
+    x: u32,
+    y: u32,
+) -> Option<(f32, f32)> {
+    let (width, height) = input.dimensions();
+    let coverage = |x: u32, y: u32| 1.0 - input.get_pixel(x, y).0[0] as f32 / u16::MAX as f32;
+    let coverage_clamped = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        coverage(x, y)
+    };
+
+    let c = coverage(x, y);
+    if c <= 0.0 {
+        return None;
+    }
+    if c >= 1.0 {
+        return Some((x as f32, y as f32));
+    }
+
+    let (xi, yi) = (x as i64, y as i64);
+    let gradient_x = (coverage_clamped(xi + 1, yi) - coverage_clamped(xi - 1, yi)) / 2.0;
+    let gradient_y = (coverage_clamped(xi, yi + 1) - coverage_clamped(xi, yi - 1)) / 2.0;
+    let gradient_magnitude = (gradient_x * gradient_x + gradient_y * gradient_y).sqrt();
+    if gradient_magnitude == 0.0 {
+        return Some((x as f32, y as f32));
     }
-    closest_location
-}
 
-fn distance_mm(location1: PixelCoordinate, location2: PixelCoordinate, pixels_per_mm: f32) -> f32 {
-    let dx = (location1.x as f32 - location2.x as f32) / pixels_per_mm;
-    let dy = (location1.y as f32 - location2.y as f32) / pixels_per_mm;
-    (dx * dx + dy * dy).sqrt()
+    let offset = 0.5 - c;
+    let unit_x = gradient_x / gradient_magnitude;
+    let unit_y = gradient_y / gradient_magnitude;
+    Some((x as f32 + offset * unit_x, y as f32 + offset * unit_y))
 }
 
 fn fade_fn(distance_to_black_mm: f32, fade_distance_mm: f32) -> u16 {
@@ -0,0 +1,195 @@
+//! Vantage-point tree over a sparse set of pixel coordinates, for nearest-neighbor queries
+//! over mostly-white inputs (text, logos, line art) where a full-image scan wastes work on
+//! empty regions. See Yianilos' "Data structures and algorithms for nearest neighbor search
+//! in general metric spaces" for the underlying construction.
+
+/// Integer pixel coordinate.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// The closest point found by a [`VpTree::nearest`] query.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NearestFeature {
+    pub point: Point,
+    pub squared_distance: f32,
+    /// Unit vector pointing from the query towards `point`. `(0.0, 0.0)` if the query
+    /// coincides with `point`. Not used by the distance-transform path, but handy for
+    /// callers that also want the direction to the nearest feature.
+    pub direction: (f32, f32),
+}
+
+struct Node {
+    point: Point,
+    /// Median distance (to `point`) of the points partitioned into `inside`. Points
+    /// further away than this went into `outside`.
+    radius: f32,
+    inside: Option<Box<Node>>,
+    outside: Option<Box<Node>>,
+}
+
+/// A vantage-point tree over a fixed set of points, supporting best-first nearest
+/// neighbor queries that prune subtrees using the triangle inequality.
+pub struct VpTree {
+    root: Option<Box<Node>>,
+}
+
+impl VpTree {
+    /// Builds a tree over `points`. Takes ownership since building recursively
+    /// partitions the point set in place.
+    pub fn build(points: Vec<Point>) -> Self {
+        VpTree {
+            root: build_node(points),
+        }
+    }
+
+    /// Returns the closest point to `query`, or `None` if the tree is empty.
+    pub fn nearest(&self, query: Point) -> Option<NearestFeature> {
+        let root = self.root.as_deref()?;
+        let mut best = closest_in(root, query, None);
+        let (point, squared_distance) = best.take()?;
+        let distance = squared_distance.sqrt();
+        let direction = if distance > 0.0 {
+            (
+                (point.x as f32 - query.x as f32) / distance,
+                (point.y as f32 - query.y as f32) / distance,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        Some(NearestFeature {
+            point,
+            squared_distance,
+            direction,
+        })
+    }
+}
+
+fn squared_distance(a: Point, b: Point) -> f32 {
+    let dx = a.x as f32 - b.x as f32;
+    let dy = a.y as f32 - b.y as f32;
+    dx * dx + dy * dy
+}
+
+fn build_node(mut points: Vec<Point>) -> Option<Box<Node>> {
+    if points.is_empty() {
+        return None;
+    }
+    // Arbitrarily use the last point as the vantage point; which point is picked doesn't
+    // affect correctness, only how balanced the resulting tree is.
+    let vantage = points.pop().unwrap();
+    if points.is_empty() {
+        return Some(Box::new(Node {
+            point: vantage,
+            radius: 0.0,
+            inside: None,
+            outside: None,
+        }));
+    }
+
+    let mut by_distance: Vec<(f32, Point)> = points
+        .into_iter()
+        .map(|point| (squared_distance(vantage, point), point))
+        .collect();
+    by_distance.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("squared distance can't be NaN"));
+
+    let median_index = by_distance.len() / 2;
+    let median_squared_distance = by_distance[median_index].0;
+    let radius = median_squared_distance.sqrt();
+    // Points tied with the median stay inside, so `outside` is strictly further away.
+    let split = by_distance.partition_point(|&(d2, _)| d2 <= median_squared_distance);
+    let outside = by_distance.split_off(split);
+    let inside = by_distance;
+
+    Some(Box::new(Node {
+        point: vantage,
+        radius,
+        inside: build_node(inside.into_iter().map(|(_, point)| point).collect()),
+        outside: build_node(outside.into_iter().map(|(_, point)| point).collect()),
+    }))
+}
+
+/// Best-first descent: visits the subtree that could contain a closer point first, and
+/// prunes the other subtree whenever the triangle inequality proves it can't beat the
+/// current best.
+fn closest_in(node: &Node, query: Point, best: Option<(Point, f32)>) -> Option<(Point, f32)> {
+    let d2 = squared_distance(node.point, query);
+    let mut best = match best {
+        Some((_, best_d2)) if best_d2 <= d2 => best,
+        _ => Some((node.point, d2)),
+    };
+
+    let distance_to_vantage = d2.sqrt();
+    let (near, far) = if distance_to_vantage <= node.radius {
+        (&node.inside, &node.outside)
+    } else {
+        (&node.outside, &node.inside)
+    };
+
+    if let Some(near) = near {
+        best = closest_in(near, query, best);
+    }
+
+    // Triangle inequality: the far side can only contain a closer point if it's possible
+    // to get from the query to the vantage point's radius boundary within `best_distance`.
+    let best_distance = best.map_or(f32::INFINITY, |(_, d2)| d2.sqrt());
+    let far_could_be_closer = (distance_to_vantage - node.radius).abs() <= best_distance;
+    if let Some(far) = far.as_ref().filter(|_| far_could_be_closer) {
+        best = closest_in(far, query, best);
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force_nearest(points: &[Point], query: Point) -> (Point, f32) {
+        points
+            .iter()
+            .map(|&point| (point, squared_distance(point, query)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn empty_tree_has_no_nearest() {
+        let tree = VpTree::build(vec![]);
+        assert_eq!(tree.nearest(Point { x: 0, y: 0 }), None);
+    }
+
+    #[test]
+    fn single_point() {
+        let tree = VpTree::build(vec![Point { x: 3, y: 4 }]);
+        let nearest = tree.nearest(Point { x: 0, y: 0 }).unwrap();
+        assert_eq!(nearest.point, Point { x: 3, y: 4 });
+        assert_eq!(nearest.squared_distance, 25.0);
+        assert_eq!(nearest.direction, (0.6, 0.8));
+    }
+
+    #[test]
+    fn matches_brute_force_over_scattered_points() {
+        let points: Vec<Point> = (0..200)
+            .map(|i| Point {
+                x: (i * 37) % 97,
+                y: (i * 53) % 61,
+            })
+            .collect();
+        let tree = VpTree::build(points.clone());
+
+        for i in 0..50 {
+            let query = Point {
+                x: (i * 13) % 97,
+                y: (i * 29) % 61,
+            };
+            let (expected_point, expected_d2) = brute_force_nearest(&points, query);
+            let got = tree.nearest(query).unwrap();
+            assert_eq!(got.squared_distance, expected_d2);
+            // Multiple points can tie for closest; only the distance is guaranteed unique.
+            assert_eq!(squared_distance(got.point, query), squared_distance(expected_point, query));
+        }
+    }
+}